@@ -0,0 +1,183 @@
+// Copyright 2014 Dawid Ciężarkiewicz
+// See LICENSE file for more information
+
+use game::Action;
+use game::{Move,Wait};
+use map::Map;
+use item::Item;
+use faction::{Faction,Player,Enemy,Wildlife};
+use hex2d;
+use hex2d::{Point,Position};
+use std::cmp;
+use std::kinds::marker::ContravariantLifetime;
+use std::rand;
+use std::rand::Rng;
+
+/// The kind of a creature, which fixes its starting stats.
+#[deriving(PartialEq,Eq,Clone,Show)]
+pub enum Race {
+    Human,
+    Scout,
+    Grunt,
+    Heavy,
+}
+
+/// An actor on the map: the player or an AI creature.
+pub struct Creature<'a> {
+    pos : Position,
+    pos_prev : Position,
+    race : Race,
+    player : bool,
+    faction : Faction,
+    hp : int,
+    max_hp : int,
+    pub inventory : Vec<Item>,
+    energy : int,
+    speed : int,
+    needs_action : bool,
+    marker : ContravariantLifetime<'a>,
+}
+
+impl<'a> Creature<'a> {
+    pub fn new(_map : &Map, pos : Position, player : bool, race : Race) -> Creature<'a> {
+        let hp = match race {
+            Human => 20,
+            Scout => 8,
+            Grunt => 14,
+            Heavy => 28,
+        };
+        // Scouts are quick, Heavies ponderous; speed is banked as energy each
+        // tick and spent on actions.
+        let speed = match race {
+            Human => 100,
+            Scout => 150,
+            Grunt => 100,
+            Heavy => 60,
+        };
+        // The player is always its own faction; otherwise the race decides it,
+        // so Scouts read as skittish wildlife that Grunts and Heavies hunt
+        // rather than everything lumping into a single player-vs-all side.
+        let faction = if player {
+            Player
+        } else {
+            match race {
+                Scout => Wildlife,
+                _ => Enemy,
+            }
+        };
+        Creature {
+            pos: pos,
+            pos_prev: pos,
+            race: race,
+            player: player,
+            faction: faction,
+            hp: hp,
+            max_hp: hp,
+            inventory: Vec::new(),
+            energy: 0,
+            speed: speed,
+            needs_action: true,
+            marker: ContravariantLifetime,
+        }
+    }
+
+    pub fn p(&self) -> &Point {
+        &self.pos.p
+    }
+
+    pub fn pos(&self) -> &Position {
+        &self.pos
+    }
+
+    pub fn pos_set(&mut self, _map : &Map, pos : Position) {
+        self.pos = pos;
+    }
+
+    pub fn pos_prev_set(&mut self, _map : &Map, pos : Position) {
+        self.pos_prev = pos;
+    }
+
+    pub fn is_player(&self) -> bool {
+        self.player
+    }
+
+    pub fn race(&self) -> Race {
+        self.race
+    }
+
+    pub fn faction(&self) -> Faction {
+        self.faction
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.hp > 0
+    }
+
+    pub fn needs_action(&self) -> bool {
+        self.needs_action
+    }
+
+    pub fn action_done(&mut self) {
+        self.needs_action = false;
+    }
+
+    pub fn update_los(&mut self, _map : &Map) {
+        self.needs_action = true;
+    }
+
+    /// Default wander behaviour: amble in a random direction, occasionally
+    /// pausing. The scheduler calls this when no reaction takes precedence.
+    pub fn tick(&mut self, _map : &Map) -> Option<Action> {
+        if self.player {
+            return None;
+        }
+        let mut rng = rand::task_rng();
+        match rng.gen_range(0u, 4) {
+            0 => Some(Wait),
+            _ => Some(Move(hex2d::all_directions[rng.gen_range(0u, 6)])),
+        }
+    }
+
+    fn attack_power(&self) -> int {
+        match self.race {
+            Human => 5,
+            Scout => 3,
+            Grunt => 5,
+            Heavy => 8,
+        }
+    }
+
+    pub fn attacked_by(&mut self, attacker : &Creature) {
+        self.hp -= attacker.attack_power();
+    }
+
+    pub fn attacked(&mut self, _target : &Creature) {
+    }
+
+    /// Apply a fixed amount of damage from a non-melee source, such as an
+    /// offensive item, without routing through the attacker's melee power.
+    pub fn take_damage(&mut self, amount : int) {
+        self.hp -= amount;
+    }
+
+    /// Restore hit points, never past the creature's maximum.
+    pub fn heal(&mut self, amount : int) {
+        self.hp = cmp::min(self.max_hp, self.hp + amount);
+    }
+
+    pub fn energy(&self) -> int {
+        self.energy
+    }
+
+    pub fn speed(&self) -> int {
+        self.speed
+    }
+
+    pub fn gain_energy(&mut self, amount : int) {
+        self.energy += amount;
+    }
+
+    pub fn spend_energy(&mut self, amount : int) {
+        self.energy -= amount;
+    }
+}
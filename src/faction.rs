@@ -0,0 +1,35 @@
+// Copyright 2014 Dawid Ciężarkiewicz
+// See LICENSE file for more information
+
+/// Which side a creature is on. Drives how it reacts to creatures it can see
+/// rather than assuming everything that isn't the player is hostile.
+#[deriving(PartialEq,Eq,Clone,Show)]
+pub enum Faction {
+    Player,
+    Enemy,
+    Wildlife,
+    Neutral,
+}
+
+/// How a creature of one faction responds to spotting another.
+#[deriving(PartialEq,Eq,Clone,Show)]
+pub enum Reaction {
+    Ignore,
+    Attack,
+    Flee,
+}
+
+/// Resolve how `a` reacts to spotting `b`.
+///
+/// Members of the same faction ignore each other; the player and the enemy
+/// faction fight on sight; enemies also hunt wildlife, which flees anything
+/// that would harm it; neutrals keep to themselves.
+pub fn reaction(a : Faction, b : Faction) -> Reaction {
+    match (a, b) {
+        (x, y) if x == y => Ignore,
+        (Player, Enemy) | (Enemy, Player) => Attack,
+        (Enemy, Wildlife) => Attack,
+        (Wildlife, Enemy) | (Wildlife, Player) => Flee,
+        _ => Ignore,
+    }
+}
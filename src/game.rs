@@ -7,7 +7,11 @@ use hex2d;
 use hex2d::{Point,Position,Direction};
 use hex2d::{Forward,Backward};
 use map::{Tile,Map};
-use map::{Wall,Floor,GlassWall,Sand};
+use map::{Floor};
+use mapgen::{BuilderChain,RandomScatterBuilder,CellularAutomata,MazeBuilder};
+use item::Item;
+use item::{loot_table,roll_loot};
+use faction::{reaction,Attack,Flee,Ignore};
 use std::rand;
 use std::rand::Rng;
 use std::cell::{RefCell};
@@ -30,14 +34,33 @@ pub enum Action {
     Turn(Direction),
     Melee(Direction),
     Use,
+    PickUp,
+    Drop,
     Wait
 }
 
+impl Action {
+    /// Energy an action costs the creature performing it. `Run` is cheaper per
+    /// tile than a deliberate `Move`, while `Melee` is the most taxing; a
+    /// creature only acts once it has banked at least this much energy.
+    pub fn cost(&self) -> int {
+        match *self {
+            Run(..) => 60,
+            Move(..) => 100,
+            Turn(..) => 50,
+            Melee(..) => 150,
+            Use | PickUp | Drop => 100,
+            Wait => 100,
+        }
+    }
+}
+
 impl<'a> GameState<'a> {
     pub fn new() -> GameState<'a> {
         let map = box hex2d::Map::new(100, 100, Tile {
             tiletype: Floor,
             creature: None,
+            item: None,
         }
         );
         GameState {
@@ -72,6 +95,12 @@ impl<'a> GameState<'a> {
         }
     }
 
+    fn spawn_at(&mut self, p : Point, player : bool, race : Race) -> Option<Rc<RefCell<Creature>>> {
+        let pos = Position { p: p, dir: hex2d::all_directions[0] };
+        let cr = box Creature::new(&*self.map, pos, player, race);
+        self.spawn(cr)
+    }
+
     fn move_creature_if_possible(&mut self, cr : &mut Creature, pos : Position) {
         let cr_p = *cr.p();
         let pos_p = pos.p;
@@ -93,6 +122,34 @@ impl<'a> GameState<'a> {
         }
     }
 
+    /// Pick an action driven by faction reactions to adjacent creatures, or
+    /// `None` to let the creature fall back to its default wander behaviour.
+    ///
+    /// The six neighbouring tiles are checked in facing-relative order so the
+    /// resulting `Direction` can be handed straight to `Melee`/`Run`.
+    fn faction_action(&self, cr : &Creature) -> Option<Action> {
+        if cr.is_player() {
+            return None;
+        }
+        let my_faction = cr.faction();
+        for (i, &rel) in hex2d::all_directions.iter().enumerate() {
+            let np = self.map.wrap(cr.p() + (cr.pos().dir + rel));
+            let other = self.map.at(np).creature.as_ref().map(|c| c.borrow().faction());
+            match other {
+                Some(of) => match reaction(my_faction, of) {
+                    Attack => return Some(Melee(rel)),
+                    Flee => {
+                        let away = hex2d::all_directions[(i + 3) % 6];
+                        return Some(Run(away));
+                    },
+                    Ignore => {}
+                },
+                None => {}
+            }
+        }
+        None
+    }
+
     pub fn tick(&mut self) {
         let mut creatures = self.creatures.clone();
 
@@ -101,17 +158,36 @@ impl<'a> GameState<'a> {
 
             match creature.as_ref().map(|cr| cr.borrow_mut())  {
                 Some(mut cr) => {
-                    if cr.needs_action() {
-                        assert!(!cr.is_player());
-                        cr.update_los(&*self.map);
-                    }
-                    let action = cr.tick(&*self.map);
-                    match action {
-                        Some(action) => {
-                            self.perform_action(&mut *cr, action);
+                    // Everyone banks energy every tick, including the player so
+                    // that speed differences apply to it too.
+                    let speed = cr.speed();
+                    cr.gain_energy(speed);
+
+                    // The player's action is driven by UI input, not here; it
+                    // still pays for it through perform_action.
+                    if !cr.is_player() {
+                        if cr.needs_action() {
+                            cr.update_los(&*self.map);
+                        }
+                        // Fast creatures spend their banked energy on several
+                        // actions before a slow creature can afford one.
+                        loop {
+                            let action = match self.faction_action(&*cr) {
+                                Some(a) => Some(a),
+                                None => cr.tick(&*self.map),
+                            };
+                            let action = match action {
+                                Some(a) => a,
+                                None => break,
+                            };
+                            if cr.energy() < action.cost() {
+                                break;
+                            }
+                            if !self.perform_action(&mut *cr, action) {
+                                break;
+                            }
                             cr.action_done();
-                        },
-                        None => {}
+                        }
                     }
                 },
                 None => { }
@@ -132,7 +208,22 @@ impl<'a> GameState<'a> {
         self.creatures = creatures;
     }
 
-    pub fn perform_action(&mut self, cr : &mut Creature, action : Action) {
+    /// Perform `action` on behalf of `cr`, charging its energy cost. Returns
+    /// `false` without doing anything if the creature hasn't banked enough
+    /// energy yet, so a UI-driven player move isn't silently swallowed — the
+    /// caller can surface "not enough energy" and keep the keypress live.
+    pub fn perform_action(&mut self, cr : &mut Creature, action : Action) -> bool {
+        // A creature can only act once it has banked enough energy. The
+        // scheduler loop already checks this for AI; gating here also covers
+        // the UI-driven player so speed differences apply to it too.
+        if cr.energy() < action.cost() {
+            return false;
+        }
+
+        // Charge the action's energy up front so both the scheduler loop and
+        // UI-driven player input pay the same toll.
+        cr.spend_energy(action.cost());
+
         let old_pos = *cr.pos();
         cr.pos_prev_set(&*self.map, old_pos);
 
@@ -148,74 +239,144 @@ impl<'a> GameState<'a> {
             },
             Melee(dir) => {
                 let target_p = self.map.wrap(cr.p() + (cr.pos().dir + dir));
-                let target = self.map.mut_at(target_p).creature.as_ref().
-                    map(|cr| cr.clone());
-                if target.is_some() {
-                    let target = target.unwrap();
-                    let target = &mut *target.borrow_mut();
-                    target.attacked_by(cr);
-                    cr.attacked(target);
-
-                    if !target.is_alive() {
-                        self.map.mut_at(target_p).creature = None;
-                    }
+                self.resolve_melee(cr, target_p);
+            },
+            Use => {
+                // Prefer consuming a carried item; otherwise grab whatever is
+                // lying on the current tile.
+                if cr.inventory.is_empty() {
+                    self.pick_up(cr);
+                } else {
+                    let item = cr.inventory.remove(0).unwrap();
+                    self.apply_item(cr, item);
+                }
+            },
+            PickUp => self.pick_up(cr),
+            Drop => {
+                match cr.inventory.pop() {
+                    Some(item) => {
+                        let p = *cr.p();
+                        self.map.mut_at(p).item = Some(item);
+                    },
+                    None => {}
                 }
             },
             _ => { }
         }
+        true
     }
 
-    pub fn randomize_map(&mut self) {
-        let height = self.map.height() as int;
-        let width = self.map.width() as int;
-        let area = width * height;
-
-        for _ in range(0, area / 12) {
-            let p = self.rng.gen::<Point>();
-            let p = self.map.wrap(p);
-
-            let t = match self.rng.gen_range(0u, 6) {
-                0 => GlassWall,
-                1 => Sand,
-                _ => Wall
-            };
-
-            self.map.mut_at(p).tiletype = t;
-            for &dir in hex2d::all_directions.iter() {
-                let p = self.map.wrap(p + dir);
-                self.map.mut_at(p).tiletype = t;
+    fn resolve_melee(&mut self, cr : &mut Creature, target_p : Point) {
+        let target = self.map.mut_at(target_p).creature.as_ref().
+            map(|cr| cr.clone());
+        if target.is_some() {
+            let target = target.unwrap();
+            let target = &mut *target.borrow_mut();
+            target.attacked_by(cr);
+            cr.attacked(target);
+
+            if !target.is_alive() {
+                let race = target.race();
+                self.creature_died(target_p, race);
             }
         }
+    }
 
-        for x in range(0i, width) {
-            let p = Point::new(x, 0);
-            self.map.mut_at(p).tiletype = Wall;
-            let p = Point::new(x, height - 1);
-            self.map.mut_at(p).tiletype = Wall;
+    /// Clear a dead creature off its tile, first rolling its race's loot table
+    /// and dropping any winnings where it fell.
+    fn creature_died(&mut self, p : Point, race : Race) {
+        let loot = roll_loot(&mut self.rng, loot_table(race).as_slice());
+        match loot {
+            // Don't clobber an item already lying where the creature fell.
+            Some(item) => {
+                if self.map.at(p).item.is_none() {
+                    self.map.mut_at(p).item = Some(item);
+                }
+            },
+            None => {}
         }
+        self.map.mut_at(p).creature = None;
+    }
 
-        for y in range(0i, height) {
-            let p = Point::new(0, y);
-            self.map.mut_at(p).tiletype = Wall;
-            let p = Point::new(width - 1, y);
-            self.map.mut_at(p).tiletype = Wall;
+    fn pick_up(&mut self, cr : &mut Creature) {
+        let p = *cr.p();
+        let item = self.map.mut_at(p).item.take();
+        match item {
+            Some(item) => cr.inventory.push(item),
+            None => {}
         }
+    }
 
+    fn apply_item(&mut self, cr : &mut Creature, item : Item) {
+        match item.heal_amount() {
+            Some(hp) => {
+                cr.heal(hp);
+                return;
+            },
+            None => {}
+        }
+        match item.damage() {
+            Some(dmg) => {
+                let target_p = self.map.wrap(cr.p() + cr.pos().dir);
+                let target = self.map.mut_at(target_p).creature.as_ref().
+                    map(|cr| cr.clone());
+                match target {
+                    Some(target) => {
+                        let target = &mut *target.borrow_mut();
+                        target.take_damage(dmg);
+                        if !target.is_alive() {
+                            let race = target.race();
+                            self.creature_died(target_p, race);
+                        }
+                    },
+                    None => {}
+                }
+            },
+            None => {}
+        }
+    }
 
+    pub fn randomize_map(&mut self) {
+        let width = self.map.width();
+        let height = self.map.height();
+        let area = (width * height) as int;
+
+        // Pick one of the available generators for this level.
+        let mut chain = BuilderChain::new();
+        match self.rng.gen_range(0u, 3) {
+            0 => chain.start_with(CellularAutomata::new()),
+            1 => chain.start_with(MazeBuilder::new()),
+            _ => chain.start_with(RandomScatterBuilder::new()),
+        }
+
+        let fresh = box hex2d::Map::new(width, height, Tile {
+            tiletype: Floor,
+            creature: None,
+            item: None,
+        });
+        let bmap = chain.build_map(&mut self.rng, fresh);
+        let starting_point = bmap.starting_point;
+        self.map = bmap.map;
 
         for _ in range(0, area / 200) {
             self.spawn_random(false, Scout);
         }
 
-        for _ in range(0, self.map.width() * self.map.height() / 400) {
+        for _ in range(0, area / 400) {
             self.spawn_random(false, Grunt);
         }
 
-        for _ in range(0, self.map.width() * self.map.height() / 800) {
+        for _ in range(0, area / 800) {
             self.spawn_random(false, Heavy);
         }
 
-        let p = self.spawn_random(true, Human);
+        let p = match starting_point {
+            Some(sp) => match self.spawn_at(sp, true, Human) {
+                Some(p) => p,
+                None => self.spawn_random(true, Human),
+            },
+            None => self.spawn_random(true, Human),
+        };
 
         self.player = Some(p.downgrade());
     }
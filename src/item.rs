@@ -0,0 +1,122 @@
+// Copyright 2014 Dawid Ciężarkiewicz
+// See LICENSE file for more information
+
+use creature::{Race,Scout,Grunt,Heavy};
+use std::rand::TaskRng;
+use std::rand::Rng;
+
+/// Something a creature can carry, pick up off the floor, or use.
+///
+/// Healing items restore hit points to the user; damage items deal their own
+/// damage amount to the creature in the facing direction (not the user's melee
+/// power), and a creature killed that way drops loot just as in melee.
+#[deriving(Clone,Show)]
+pub enum Item {
+    HealthPotion(int),
+    DamageScroll(int),
+}
+
+impl Item {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            HealthPotion(_) => "health potion",
+            DamageScroll(_) => "damage scroll",
+        }
+    }
+
+    /// How much this item heals when used, if it is a healing item.
+    pub fn heal_amount(&self) -> Option<int> {
+        match *self {
+            HealthPotion(hp) => Some(hp),
+            _ => None,
+        }
+    }
+
+    /// How much damage this item deals when used, if it is an offensive item.
+    pub fn damage(&self) -> Option<int> {
+        match *self {
+            DamageScroll(dmg) => Some(dmg),
+            _ => None,
+        }
+    }
+}
+
+/// One weighted row of a loot table. A `None` item is a "dropped nothing"
+/// outcome, which lets a table make drops probabilistic without a separate
+/// success roll.
+pub struct LootEntry {
+    pub weight : uint,
+    pub item : Option<Item>,
+}
+
+/// The weighted drop table for a race. Tougher races carry better odds and
+/// better items, so killing a `Heavy` is worth more than swatting a `Scout`.
+pub fn loot_table(race : Race) -> Vec<LootEntry> {
+    match race {
+        Scout => vec![
+            LootEntry { weight: 8, item: None },
+            LootEntry { weight: 2, item: Some(HealthPotion(5)) },
+        ],
+        Grunt => vec![
+            LootEntry { weight: 6, item: None },
+            LootEntry { weight: 3, item: Some(HealthPotion(8)) },
+            LootEntry { weight: 1, item: Some(DamageScroll(6)) },
+        ],
+        Heavy => vec![
+            LootEntry { weight: 3, item: None },
+            LootEntry { weight: 3, item: Some(HealthPotion(12)) },
+            LootEntry { weight: 4, item: Some(DamageScroll(10)) },
+        ],
+        _ => Vec::new(),
+    }
+}
+
+/// Roll a single drop from a table by summing weights, drawing in `[0, total)`,
+/// and walking the cumulative sum to the selected entry.
+pub fn roll_loot(rng : &mut TaskRng, table : &[LootEntry]) -> Option<Item> {
+    let total = table.iter().fold(0u, |acc, e| acc + e.weight);
+    if total == 0 {
+        return None;
+    }
+    let mut roll = rng.gen_range(0u, total);
+    for e in table.iter() {
+        if roll < e.weight {
+            return e.item.clone();
+        }
+        roll -= e.weight;
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::{roll_loot,LootEntry};
+    use super::HealthPotion;
+    use std::rand;
+
+    #[test]
+    fn empty_table_drops_nothing() {
+        let mut rng = rand::task_rng();
+        let table : Vec<LootEntry> = Vec::new();
+        assert!(roll_loot(&mut rng, table.as_slice()).is_none());
+    }
+
+    #[test]
+    fn all_none_entries_never_drop() {
+        let mut rng = rand::task_rng();
+        let table = vec![LootEntry { weight: 3, item: None }];
+        assert!(roll_loot(&mut rng, table.as_slice()).is_none());
+    }
+
+    #[test]
+    fn single_item_entry_always_drops_it() {
+        let mut rng = rand::task_rng();
+        let table = vec![LootEntry { weight: 1, item: Some(HealthPotion(5)) }];
+        for _ in range(0u, 20) {
+            match roll_loot(&mut rng, table.as_slice()) {
+                Some(HealthPotion(5)) => {},
+                other => fail!("unexpected roll: {}", other),
+            }
+        }
+    }
+}
@@ -0,0 +1,47 @@
+// Copyright 2014 Dawid Ciężarkiewicz
+// See LICENSE file for more information
+
+use hex2d;
+use creature::Creature;
+use item::Item;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+/// What a tile is made of. `GlassWall` blocks movement but not sight.
+#[deriving(PartialEq,Eq,Clone,Show)]
+pub enum TileType {
+    Floor,
+    Wall,
+    GlassWall,
+    Sand,
+}
+
+/// A single map cell: its terrain, any creature standing on it, and any item
+/// lying on the ground waiting to be picked up.
+pub struct Tile<'a> {
+    pub tiletype : TileType,
+    pub creature : Option<Rc<RefCell<Creature<'a>>>>,
+    pub item : Option<Item>,
+}
+
+impl<'a> Clone for Tile<'a> {
+    fn clone(&self) -> Tile<'a> {
+        Tile {
+            tiletype: self.tiletype.clone(),
+            creature: self.creature.clone(),
+            item: self.item.clone(),
+        }
+    }
+}
+
+impl<'a> Tile<'a> {
+    pub fn is_passable(&self) -> bool {
+        match self.tiletype {
+            Wall | GlassWall => false,
+            _ => true,
+        }
+    }
+}
+
+/// The playfield: a hex grid of `Tile`s.
+pub type Map<'a> = hex2d::Map<Tile<'a>>;
@@ -0,0 +1,459 @@
+// Copyright 2014 Dawid Ciężarkiewicz
+// See LICENSE file for more information
+
+use hex2d;
+use hex2d::{Point,Position};
+use map::{Tile,Map};
+use map::{Wall,Floor,GlassWall,Sand};
+use std::rand::TaskRng;
+use std::rand::Rng;
+
+/// An axis-aligned block of cells in map coordinates.
+///
+/// Generators that lay out rooms record them here so later modifiers (and the
+/// eventual spawner) can reason about where open space was carved.
+pub struct Rect {
+    pub x1 : int,
+    pub y1 : int,
+    pub x2 : int,
+    pub y2 : int,
+}
+
+impl Rect {
+    pub fn new(x : int, y : int, w : int, h : int) -> Rect {
+        Rect { x1: x, y1: y, x2: x + w, y2: y + h }
+    }
+
+    pub fn center(&self) -> Point {
+        Point::new((self.x1 + self.x2) / 2, (self.y1 + self.y2) / 2)
+    }
+}
+
+/// Working state threaded through a `BuilderChain`.
+///
+/// It owns the `Map` while it is being generated and accumulates the metadata
+/// the generators produce: where the player should start, where the level
+/// exits, and the rooms/corridors that were carved.
+pub struct BuilderMap<'a> {
+    pub map : Box<Map<'a>>,
+    pub starting_point : Option<Point>,
+    pub exit_point : Option<Point>,
+    pub rooms : Vec<Rect>,
+    pub corridors : Vec<Vec<Point>>,
+}
+
+impl<'a> BuilderMap<'a> {
+    pub fn new(map : Box<Map<'a>>) -> BuilderMap<'a> {
+        BuilderMap {
+            map: map,
+            starting_point: None,
+            exit_point: None,
+            rooms: Vec::new(),
+            corridors: Vec::new(),
+        }
+    }
+
+    /// Pick a random passable tile, mirroring `GameState::spawn_random`'s
+    /// rejection loop, for generators that just need somewhere to stand.
+    pub fn random_passable(&self, rng : &mut TaskRng) -> Point {
+        loop {
+            let p = self.map.wrap(rng.gen::<Point>());
+            if self.map.at(p).is_passable() {
+                return p;
+            }
+        }
+    }
+}
+
+/// The builder that lays down the initial tile layout.
+///
+/// Kept separate from `MapModifier` so a chain always starts from a complete
+/// map before the ordered modifiers refine it.
+pub trait InitialMapBuilder {
+    fn build(&self, rng : &mut TaskRng, map : &mut BuilderMap);
+}
+
+/// A single reusable step that refines an already-built map.
+pub trait MapModifier {
+    fn modify(&self, rng : &mut TaskRng, map : &mut BuilderMap);
+}
+
+/// Runs one `InitialMapBuilder` followed by ordered `MapModifier`s and hands
+/// the finished `BuilderMap` back to the caller.
+pub struct BuilderChain {
+    starter : Option<Box<InitialMapBuilder>>,
+    modifiers : Vec<Box<MapModifier>>,
+}
+
+impl BuilderChain {
+    pub fn new() -> BuilderChain {
+        BuilderChain { starter: None, modifiers: Vec::new() }
+    }
+
+    pub fn start_with(&mut self, builder : Box<InitialMapBuilder>) {
+        self.starter = Some(builder);
+    }
+
+    pub fn with(&mut self, modifier : Box<MapModifier>) {
+        self.modifiers.push(modifier);
+    }
+
+    pub fn build_map<'a>(&self, rng : &mut TaskRng, map : Box<Map<'a>>) -> BuilderMap<'a> {
+        let mut bmap = BuilderMap::new(map);
+        match self.starter {
+            Some(ref starter) => starter.build(rng, &mut bmap),
+            None => fail!("BuilderChain without an initial builder"),
+        }
+        for modifier in self.modifiers.iter() {
+            modifier.modify(rng, &mut bmap);
+        }
+        bmap
+    }
+}
+
+/// Grows organic caves with a cellular-automata smoothing pass.
+///
+/// The six-neighbour hex adjacency is sparser than a square grid's eight, so the
+/// birth/death thresholds are tuned for it: a floor with >=5 wall neighbours
+/// fills in, a wall with <=2 opens up. After smoothing, tiles unreachable
+/// from the start are sealed off so the player can never be boxed out.
+pub struct CellularAutomata;
+
+impl CellularAutomata {
+    pub fn new() -> Box<CellularAutomata> {
+        box CellularAutomata
+    }
+
+    fn count_wall_neighbours(&self, map : &BuilderMap, p : Point) -> uint {
+        let mut walls = 0u;
+        for &dir in hex2d::all_directions.iter() {
+            let n = map.map.wrap(p + dir);
+            if map.map.at(n).tiletype == Wall {
+                walls += 1;
+            }
+        }
+        walls
+    }
+}
+
+impl InitialMapBuilder for CellularAutomata {
+    fn build(&self, rng : &mut TaskRng, map : &mut BuilderMap) {
+        let width = map.map.width() as int;
+        let height = map.map.height() as int;
+
+        // Random fill: interior tiles ~45% wall, outer ring always wall.
+        for y in range(0i, height) {
+            for x in range(0i, width) {
+                let p = Point::new(x, y);
+                if x == 0 || x == width - 1 || y == 0 || y == height - 1 {
+                    map.map.mut_at(p).tiletype = Wall;
+                } else if rng.gen_range(0u, 100) < 45 {
+                    map.map.mut_at(p).tiletype = Wall;
+                } else {
+                    map.map.mut_at(p).tiletype = Floor;
+                }
+            }
+        }
+
+        // Smoothing iterations against the six hex neighbours.
+        for _ in range(0i, 5) {
+            let mut next = Vec::new();
+            for y in range(1i, height - 1) {
+                for x in range(1i, width - 1) {
+                    let p = Point::new(x, y);
+                    let walls = self.count_wall_neighbours(map, p);
+                    let t = if map.map.at(p).tiletype == Wall {
+                        if walls <= 2 { Floor } else { Wall }
+                    } else {
+                        if walls >= 5 { Wall } else { Floor }
+                    };
+                    next.push((p, t));
+                }
+            }
+            for &(p, t) in next.iter() {
+                map.map.mut_at(p).tiletype = t;
+            }
+        }
+
+        // Flood-fill from a passable start and seal any unreachable pockets.
+        let start = map.random_passable(rng);
+        let mut reachable = Vec::new();
+        for _ in range(0i, width * height) {
+            reachable.push(false);
+        }
+        let idx = |p : Point| -> uint { (p.y * width + p.x) as uint };
+        let mut stack = vec![start];
+        *reachable.get_mut(idx(start)) = true;
+        loop {
+            let p = match stack.pop() {
+                Some(p) => p,
+                None => break,
+            };
+            for &dir in hex2d::all_directions.iter() {
+                let n = map.map.wrap(p + dir);
+                let ni = idx(n);
+                if !*reachable.get(ni) && map.map.at(n).is_passable() {
+                    *reachable.get_mut(ni) = true;
+                    stack.push(n);
+                }
+            }
+        }
+        for y in range(0i, height) {
+            for x in range(0i, width) {
+                let p = Point::new(x, y);
+                if !*reachable.get(idx(p)) && map.map.at(p).is_passable() {
+                    map.map.mut_at(p).tiletype = Wall;
+                }
+            }
+        }
+
+        map.starting_point = Some(start);
+    }
+}
+
+impl MapModifier for CellularAutomata {
+    fn modify(&self, rng : &mut TaskRng, map : &mut BuilderMap) {
+        self.build(rng, map);
+    }
+}
+
+/// Carves a perfect maze on the hex grid with a recursive backtracker.
+///
+/// Cells sit two tiles apart; each tracks whether it has been visited and which
+/// of its six hex-direction walls are open. Starting from a random cell the
+/// carver repeatedly picks an unvisited neighbour, knocks down the intermediate
+/// tile, and recurses, backtracking over a stack when it dead-ends. The origin
+/// cell becomes the start and the last cell carved becomes the exit.
+pub struct MazeBuilder;
+
+/// One cell of the coarse maze lattice.
+struct Cell {
+    visited : bool,
+    walls : [bool, ..6],
+}
+
+impl MazeBuilder {
+    pub fn new() -> Box<MazeBuilder> {
+        box MazeBuilder
+    }
+}
+
+impl InitialMapBuilder for MazeBuilder {
+    fn build(&self, rng : &mut TaskRng, map : &mut BuilderMap) {
+        let width = map.map.width() as int;
+        let height = map.map.height() as int;
+
+        // Coarse lattice: one cell per two tiles in each axis.
+        let cols = (width - 1) / 2;
+        let rows = (height - 1) / 2;
+        if cols <= 0 || rows <= 0 {
+            return;
+        }
+
+        // Start from solid rock and carve passages out of it.
+        for y in range(0i, height) {
+            for x in range(0i, width) {
+                map.map.mut_at(Point::new(x, y)).tiletype = Wall;
+            }
+        }
+
+        let mut cells = Vec::new();
+        for _ in range(0, cols * rows) {
+            cells.push(Cell { visited: false, walls: [true, ..6] });
+        }
+        let cell_idx = |cx : int, cy : int| -> uint { (cy * cols + cx) as uint };
+        // The tile a cell occupies.
+        let tile_of = |cx : int, cy : int| -> Point { Point::new(cx * 2 + 1, cy * 2 + 1) };
+
+        let start_cx = rng.gen_range(0, cols);
+        let start_cy = rng.gen_range(0, rows);
+        let origin = tile_of(start_cx, start_cy);
+
+        cells.get_mut(cell_idx(start_cx, start_cy)).visited = true;
+        map.map.mut_at(origin).tiletype = Floor;
+
+        let mut stack = vec![(start_cx, start_cy)];
+        let mut last = (start_cx, start_cy);
+
+        loop {
+            let (cx, cy) = match stack.last() {
+                Some(&c) => c,
+                None => break,
+            };
+
+            // Collect unvisited neighbours, paired with the hex direction that
+            // reaches them and its index into the cell's wall slots.
+            let mut neighbours = Vec::new();
+            for (dir_i, &dir) in hex2d::all_directions.iter().enumerate() {
+                // Cells sit two tiles apart: the neighbour cell centre is two
+                // hex steps away, with the wall tile sitting one step between.
+                let here = tile_of(cx, cy);
+                let ncell = map.map.wrap(here + dir + dir);
+                let ncx = (ncell.x - 1) / 2;
+                let ncy = (ncell.y - 1) / 2;
+                let aligned = ncx * 2 + 1 == ncell.x && ncy * 2 + 1 == ncell.y;
+                if aligned && ncx >= 0 && ncx < cols && ncy >= 0 && ncy < rows
+                    && !cells.get(cell_idx(ncx, ncy)).visited {
+                    neighbours.push((dir_i, dir, ncx, ncy));
+                }
+            }
+
+            if neighbours.is_empty() {
+                stack.pop();
+                continue;
+            }
+
+            let pick = rng.gen_range(0, neighbours.len());
+            let (dir_i, dir, ncx, ncy) = *neighbours.get(pick);
+
+            // Knock down the shared wall by carving the intermediate tile.
+            let here = tile_of(cx, cy);
+            let between = map.map.wrap(here + dir);
+            map.map.mut_at(between).tiletype = Floor;
+            map.map.mut_at(tile_of(ncx, ncy)).tiletype = Floor;
+
+            cells.get_mut(cell_idx(cx, cy)).walls[dir_i] = false;
+            {
+                let n = cells.get_mut(cell_idx(ncx, ncy));
+                n.visited = true;
+                n.walls[(dir_i + 3) % 6] = false;
+            }
+
+            last = (ncx, ncy);
+            stack.push((ncx, ncy));
+        }
+
+        let (lcx, lcy) = last;
+        map.starting_point = Some(origin);
+        map.exit_point = Some(tile_of(lcx, lcy));
+    }
+}
+
+/// The original scatter-and-border layout, preserved as a reusable builder.
+///
+/// Scatters clusters of `Wall`/`GlassWall`/`Sand` across the interior and walls
+/// off the outer ring, then drops the player's start on a random passable tile.
+pub struct RandomScatterBuilder;
+
+impl RandomScatterBuilder {
+    pub fn new() -> Box<RandomScatterBuilder> {
+        box RandomScatterBuilder
+    }
+}
+
+impl InitialMapBuilder for RandomScatterBuilder {
+    fn build(&self, rng : &mut TaskRng, map : &mut BuilderMap) {
+        let height = map.map.height() as int;
+        let width = map.map.width() as int;
+        let area = width * height;
+
+        for _ in range(0, area / 12) {
+            let p = map.map.wrap(rng.gen::<Point>());
+
+            let t = match rng.gen_range(0u, 6) {
+                0 => GlassWall,
+                1 => Sand,
+                _ => Wall
+            };
+
+            map.map.mut_at(p).tiletype = t;
+            for &dir in hex2d::all_directions.iter() {
+                let p = map.map.wrap(p + dir);
+                map.map.mut_at(p).tiletype = t;
+            }
+        }
+
+        for x in range(0i, width) {
+            let p = Point::new(x, 0);
+            map.map.mut_at(p).tiletype = Wall;
+            let p = Point::new(x, height - 1);
+            map.map.mut_at(p).tiletype = Wall;
+        }
+
+        for y in range(0i, height) {
+            let p = Point::new(0, y);
+            map.map.mut_at(p).tiletype = Wall;
+            let p = Point::new(width - 1, y);
+            map.map.mut_at(p).tiletype = Wall;
+        }
+
+        let start = map.random_passable(rng);
+        map.starting_point = Some(start);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BuilderMap,CellularAutomata,MazeBuilder};
+    use super::{MapModifier,InitialMapBuilder};
+    use hex2d;
+    use hex2d::Point;
+    use map::{Tile,Floor};
+    use std::rand;
+
+    fn blank_map<'a>(w : uint, h : uint) -> BuilderMap<'a> {
+        let m = box hex2d::Map::new(w, h, Tile {
+            tiletype: Floor,
+            creature: None,
+            item: None,
+        });
+        BuilderMap::new(m)
+    }
+
+    #[test]
+    fn cave_start_is_passable_and_every_floor_reachable() {
+        let mut rng = rand::task_rng();
+        let mut bmap = blank_map(40, 40);
+        CellularAutomata.modify(&mut rng, &mut bmap);
+
+        let start = bmap.starting_point.expect("cave must set a start");
+        assert!(bmap.map.at(start).is_passable());
+
+        // The seal pass must leave no passable tile cut off from the start.
+        let w = bmap.map.width() as int;
+        let h = bmap.map.height() as int;
+        let idx = |p : Point| -> uint { (p.y * w + p.x) as uint };
+        let mut seen = Vec::from_elem((w * h) as uint, false);
+        let mut stack = vec![start];
+        *seen.get_mut(idx(start)) = true;
+        loop {
+            let p = match stack.pop() { Some(p) => p, None => break };
+            for &dir in hex2d::all_directions.iter() {
+                let n = bmap.map.wrap(p + dir);
+                if !*seen.get(idx(n)) && bmap.map.at(n).is_passable() {
+                    *seen.get_mut(idx(n)) = true;
+                    stack.push(n);
+                }
+            }
+        }
+        for y in range(0i, h) {
+            for x in range(0i, w) {
+                let p = Point::new(x, y);
+                assert!(!bmap.map.at(p).is_passable() || *seen.get(idx(p)));
+            }
+        }
+    }
+
+    #[test]
+    fn maze_carves_more_than_the_origin() {
+        let mut rng = rand::task_rng();
+        let mut bmap = blank_map(21, 21);
+        MazeBuilder.build(&mut rng, &mut bmap);
+
+        let start = bmap.starting_point.expect("maze must set a start");
+        let exit = bmap.exit_point.expect("maze must set an exit");
+        assert!(start != exit);
+
+        let w = bmap.map.width() as int;
+        let h = bmap.map.height() as int;
+        let mut floors = 0u;
+        for y in range(0i, h) {
+            for x in range(0i, w) {
+                if bmap.map.at(Point::new(x, y)).is_passable() {
+                    floors += 1;
+                }
+            }
+        }
+        assert!(floors > 1);
+    }
+}